@@ -8,10 +8,40 @@ use eyre::{Result, WrapErr, eyre};
 use std::path::{Path, PathBuf};
 use std::env;
 use std::io::{stdin, stdout, Write};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::fs::{read, write, create_dir_all};
+use tokio::fs::{read, create_dir_all};
 use tokio::time::{sleep, Duration};
+use chrono::Utc;
 use hex;
+use walkdir::WalkDir;
+use sha2::{Digest, Sha256};
+use std::io::BufWriter;
+use std::fs::File;
+use argon2::Argon2;
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, KeyInit, Nonce};
+use rand::RngCore;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use tokio::sync::Mutex as AsyncMutex;
+use lru::LruCache;
+use std::sync::Mutex as SyncMutex;
+use std::ffi::OsStr;
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, Request,
+};
+use libc::{EACCES, EIO, EISDIR, ENOENT, ENOTDIR};
+use serde_json;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -28,6 +58,12 @@ enum Commands {
     Archive(ArchiveArgs),
     /// Download a file (using DataAddress) or the contents of an archive (using ArchiveAddress)
     Download(DownloadArgs),
+    /// List previously uploaded files and archives recorded in the upload ledger
+    List(ListArgs),
+    /// Serve an archive's contents over local HTTP, fetching files lazily on first request
+    Serve(ServeArgs),
+    /// Mount an archive as a read-only FUSE filesystem, fetching files lazily on first read
+    Mount(MountArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -39,6 +75,11 @@ struct UploadArgs {
     /// Optional: Directory to download the file to during verification
     #[arg(short, long, default_value = ".")]
     output_dir: PathBuf,
+
+    /// Encrypt the data with a passphrase before uploading (also triggered
+    /// implicitly by setting the GEMS_PASSPHRASE environment variable)
+    #[arg(long)]
+    encrypt: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -67,6 +108,49 @@ struct DownloadArgs {
     /// Treat the address as an ArchiveAddress and download all its contents
     #[arg(long)]
     archive: bool,
+
+    /// Expected sha256 digest (hex) of the downloaded data, for when the
+    /// caller has it out of band. Overrides the digest recorded in the
+    /// upload ledger, if any.
+    #[arg(long)]
+    verify_hash: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct ListArgs {
+    /// Only show ledger entries whose original path contains this substring
+    #[arg(short, long)]
+    filter: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct ServeArgs {
+    /// The ArchiveAddress (as hex string) to serve
+    #[arg(index = 1)]
+    address_hex: String,
+
+    /// Local port to listen on
+    #[arg(short, long, default_value_t = 8080)]
+    port: u16,
+
+    /// Maximum number of fetched files to keep in the in-memory LRU cache
+    #[arg(long, default_value_t = 64)]
+    cache_size: usize,
+}
+
+#[derive(Parser, Debug)]
+struct MountArgs {
+    /// The ArchiveAddress (as hex string) to mount
+    #[arg(index = 1)]
+    address_hex: String,
+
+    /// Directory to mount the archive at
+    #[arg(index = 2)]
+    mountpoint: PathBuf,
+
+    /// Approximate memory budget, in megabytes, for cached file contents
+    #[arg(long, default_value_t = 256)]
+    cache_budget_mb: u64,
 }
 
 // Helper function for interactive prompts
@@ -84,36 +168,369 @@ fn ask_yes_no(prompt: &str) -> Result<bool> {
     }
 }
 
-// Function to use PublicArchive and add retries
-async fn perform_archive_action(
-    client: &Client,
-    payment: PaymentOption,
-    data_addr: &DataAddress,
+// --- Client-side Encryption ---
+// Transparent, optional encryption so users can store private data on the
+// public network. The uploaded blob is self-describing: a small fixed
+// header carries everything (other than the passphrase) needed to decrypt
+// it again, so `handle_download` can detect and reverse it without the
+// caller having to say which files were encrypted.
+
+const ENC_MAGIC: &[u8; 4] = b"GEM1";
+const ENC_VERSION: u8 = 1;
+const ENC_SALT_LEN: usize = 16;
+const ENC_NONCE_LEN: usize = 12;
+const ENC_HEADER_LEN: usize = ENC_MAGIC.len() + 1 + ENC_SALT_LEN + ENC_NONCE_LEN;
+
+fn has_encryption_header(data: &[u8]) -> bool {
+    data.len() >= ENC_HEADER_LEN && &data[0..4] == ENC_MAGIC && data[4] == ENC_VERSION
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| eyre!("Failed to derive encryption key from passphrase: {}", e))?;
+    Ok(key)
+}
+
+// Reads GEMS_PASSPHRASE if set, otherwise prompts on stdin (plaintext, in
+// keeping with this CLI's existing prompt style).
+fn resolve_passphrase() -> Result<String> {
+    if let Ok(passphrase) = env::var("GEMS_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+
+    print!("Enter passphrase for encryption: ");
+    stdout().flush().wrap_err("Failed to flush stdout")?;
+    let mut input = String::new();
+    stdin().read_line(&mut input).wrap_err("Failed to read passphrase")?;
+    let passphrase = input.trim().to_string();
+    if passphrase.is_empty() {
+        return Err(eyre!("Passphrase cannot be empty"));
+    }
+    Ok(passphrase)
+}
+
+// Encrypts `plaintext` with a key derived from `passphrase` via Argon2id and
+// a random salt, then ChaCha20-Poly1305 with a random nonce. Returns
+// `header || ciphertext`, where the header is `magic || version || salt ||
+// nonce` so the blob can describe its own decryption parameters.
+fn encrypt_bytes(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; ENC_SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let mut nonce_bytes = [0u8; ENC_NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce_bytes);
+
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = ChaCha20Poly1305::new(&key.into());
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    let ciphertext = cipher
+        .encrypt(nonce, plaintext)
+        .map_err(|e| eyre!("Encryption failed: {}", e))?;
+
+    let mut out = Vec::with_capacity(ENC_HEADER_LEN + ciphertext.len());
+    out.extend_from_slice(ENC_MAGIC);
+    out.push(ENC_VERSION);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce_bytes);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+// Reverses `encrypt_bytes`. The Poly1305 tag is verified as part of
+// decryption, so a wrong passphrase or tampered blob is rejected rather than
+// silently producing garbage plaintext.
+fn decrypt_bytes(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    if !has_encryption_header(data) {
+        return Err(eyre!("Data does not carry a recognized gems encryption header"));
+    }
+
+    let salt = &data[5..5 + ENC_SALT_LEN];
+    let nonce_bytes = &data[5 + ENC_SALT_LEN..ENC_HEADER_LEN];
+    let ciphertext = &data[ENC_HEADER_LEN..];
+
+    let key = derive_key(passphrase, salt)?;
+    let cipher = ChaCha20Poly1305::new(&key.into());
+    let nonce = Nonce::from_slice(nonce_bytes);
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|_| eyre!("Decryption failed: wrong passphrase, or the data is corrupted/tampered"))
+}
+
+// Decrypts `data` if it carries the gems encryption header, prompting for
+// (or reading GEMS_PASSPHRASE for) the passphrase at most once per call site
+// by caching it in `cached_passphrase` across repeated calls, e.g. while
+// downloading many entries from one archive.
+fn decrypt_if_needed(data: Bytes, cached_passphrase: &mut Option<String>) -> Result<Bytes> {
+    if !has_encryption_header(&data) {
+        return Ok(data);
+    }
+    if cached_passphrase.is_none() {
+        *cached_passphrase = Some(resolve_passphrase()?);
+    }
+    let passphrase = cached_passphrase.as_ref().expect("just set above");
+    Ok(Bytes::from(decrypt_bytes(&data, passphrase)?))
+}
+
+fn data_address_from_hex(address_hex: &str) -> Result<DataAddress> {
+    let xorname_bytes = hex::decode(address_hex)
+        .wrap_err("Invalid hex string for DataAddress XorName")?;
+    let xorname_array: [u8; 32] = xorname_bytes.as_slice().try_into()
+        .map_err(|_| eyre!("Hex string does not represent a valid XorName (expected 32 bytes, got {})", xorname_bytes.len()))?;
+    Ok(DataAddress::new(XorName(xorname_array)))
+}
+
+// --- Upload Ledger ---
+// A single human-readable, append-only, line-oriented log of everything we've
+// ever paid to store, so addresses are recoverable even if the terminal
+// scrollback that printed them is long gone.
+
+const LEDGER_FIELD_SEP: &str = "\t";
+
+enum LedgerEntryType {
+    Data,
+    Archive,
+}
+
+impl LedgerEntryType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LedgerEntryType::Data => "data",
+            LedgerEntryType::Archive => "archive",
+        }
+    }
+}
+
+fn ledger_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| eyre!("Could not determine home directory"))?;
+    let dir = home.join(".gems");
+    std::fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create ledger directory: {:?}", dir))?;
+    Ok(dir.join("uploads.log"))
+}
+
+// Appends one line to the ledger: timestamp, entry type, original path, hex
+// address, cost in AttoTokens, and (for data entries) the sha256 digest of
+// the plaintext so downloads can later verify integrity. Tab-separated so it
+// stays greppable. The digest field is "-" when there isn't one.
+fn append_ledger_entry(
+    entry_type: LedgerEntryType,
     original_path: &Path,
-    metadata: &Metadata
-) -> Result<ArchiveAddress> {
-    println!("--- Performing Archive Action (using PublicArchive) ---");
-    println!("Creating new archive for DataAddress: {:?} (original path: {:?})", data_addr, original_path);
+    address_hex: &str,
+    cost: AttoTokens,
+    sha256_hex: Option<&str>,
+) -> Result<()> {
+    let path = ledger_path()?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .wrap_err_with(|| format!("Failed to open ledger file: {:?}", path))?;
 
-    let mut archive = PublicArchive::new();
-    let archive_path = original_path.file_name()
-        .ok_or_else(|| eyre!("Could not get filename for archive path"))?
-        .into();
-    archive.add_file(archive_path, *data_addr, metadata.clone());
+    let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+    writeln!(
+        file,
+        "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}",
+        timestamp,
+        entry_type.as_str(),
+        original_path.display(),
+        address_hex,
+        cost,
+        sha256_hex.unwrap_or("-"),
+        sep = LEDGER_FIELD_SEP
+    )
+    .wrap_err("Failed to append entry to ledger")?;
+
+    Ok(())
+}
+
+// Scans the ledger for an entry matching `address_hex` and returns its
+// recorded sha256 digest, if any, so downloads can verify integrity without
+// the caller having to supply `--verify-hash` out of band.
+fn lookup_expected_hash(address_hex: &str) -> Result<Option<String>> {
+    let path = ledger_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file = std::fs::File::open(&path)
+        .wrap_err_with(|| format!("Failed to open ledger file: {:?}", path))?;
+    let reader = BufReader::new(file);
+
+    for line in reader.lines() {
+        let line = line.wrap_err("Failed to read line from ledger")?;
+        let fields: Vec<&str> = line.split(LEDGER_FIELD_SEP).collect();
+        if fields.len() != 6 {
+            continue;
+        }
+        let (entry_address_hex, digest) = (fields[3], fields[5]);
+        if digest != "-" && entry_address_hex.eq_ignore_ascii_case(address_hex) {
+            return Ok(Some(digest.to_string()));
+        }
+    }
+
+    Ok(None)
+}
+
+// --- Dedup Index ---
+// A small on-disk key-value store mapping a content's sha256 digest to the
+// DataAddress it's already stored at (plus the cost paid for it), so repeat
+// uploads of identical content can skip `data_put_public` entirely instead
+// of paying for the same bytes twice. Rebuilt from the upload ledger the
+// first time it's needed, then kept up to date incrementally.
+
+fn dedup_index_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| eyre!("Could not determine home directory"))?;
+    let dir = home.join(".gems");
+    std::fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create dedup index directory: {:?}", dir))?;
+    Ok(dir.join("dedup_index.json"))
+}
+
+// Maps sha256 hex digest -> (DataAddress hex, cost paid, as displayed in the ledger).
+type DedupIndex = HashMap<String, (String, String)>;
+
+fn load_dedup_index() -> Result<DedupIndex> {
+    let path = dedup_index_path()?;
+    if path.exists() {
+        let content = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Failed to read dedup index: {:?}", path))?;
+        return serde_json::from_str(&content)
+            .wrap_err_with(|| format!("Failed to parse dedup index: {:?}", path));
+    }
+
+    // First run: populate the index from whatever the ledger already knows.
+    let mut index = DedupIndex::new();
+    let ledger = ledger_path()?;
+    if ledger.exists() {
+        let file = std::fs::File::open(&ledger)
+            .wrap_err_with(|| format!("Failed to open ledger file: {:?}", ledger))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.wrap_err("Failed to read line from ledger")?;
+            let fields: Vec<&str> = line.split(LEDGER_FIELD_SEP).collect();
+            if fields.len() != 6 {
+                continue;
+            }
+            let (kind, address_hex, cost, sha256_hex) = (fields[1], fields[3], fields[4], fields[5]);
+            if kind == LedgerEntryType::Data.as_str() && sha256_hex != "-" {
+                index.entry(sha256_hex.to_string()).or_insert_with(|| (address_hex.to_string(), cost.to_string()));
+            }
+        }
+    }
+    save_dedup_index(&index)?;
+    Ok(index)
+}
+
+fn save_dedup_index(index: &DedupIndex) -> Result<()> {
+    let path = dedup_index_path()?;
+    let content = serde_json::to_string_pretty(index).wrap_err("Failed to serialize dedup index")?;
+    std::fs::write(&path, content).wrap_err_with(|| format!("Failed to write dedup index: {:?}", path))?;
+    Ok(())
+}
+
+// Reads the ledger and pretty-prints every entry, optionally filtered by a
+// substring of the original path.
+fn print_ledger(filter: Option<&str>) -> Result<()> {
+    let path = ledger_path()?;
+    if !path.exists() {
+        println!("No uploads recorded yet ({:?} does not exist).", path);
+        return Ok(());
+    }
+
+    let file = std::fs::File::open(&path)
+        .wrap_err_with(|| format!("Failed to open ledger file: {:?}", path))?;
+    let reader = BufReader::new(file);
+
+    let mut shown = 0;
+    for line in reader.lines() {
+        let line = line.wrap_err("Failed to read line from ledger")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(LEDGER_FIELD_SEP).collect();
+        let [timestamp, kind, original_path, address_hex, cost, sha256_hex] = fields[..] else {
+            println!("  (skipping malformed ledger line: {})", line);
+            continue;
+        };
+
+        if let Some(needle) = filter {
+            if !original_path.contains(needle) {
+                continue;
+            }
+        }
+
+        print!(
+            "{}  [{}]  {}\n    -> {}  ({} AttoTokens)",
+            timestamp, kind, original_path, address_hex, cost
+        );
+        if sha256_hex != "-" {
+            print!("  sha256:{}", sha256_hex);
+        }
+        println!();
+        shown += 1;
+    }
+
+    if shown == 0 {
+        println!("No matching uploads found.");
+    }
+
+    Ok(())
+}
+
+async fn handle_list(args: ListArgs) -> Result<()> {
+    print_ledger(args.filter.as_deref())
+}
+
+// Uploads a single blob with retries, independent of whatever else is being
+// uploaded around it, so one transient failure in a batch doesn't abort the
+// rest of the batch.
+async fn upload_bytes_with_retries(
+    client: &Client,
+    payment: &PaymentOption,
+    data: Bytes,
+    label: &str,
+) -> Result<(AttoTokens, DataAddress)> {
+    let max_retries = 50;
+
+    for attempt in 1..=max_retries {
+        println!("  --- Upload Attempt {}/{} ({}) ---", attempt, max_retries, label);
+        match client.data_put_public(data.clone(), payment.clone()).await {
+            Ok((cost, data_addr)) => {
+                println!("  Successfully uploaded {} on attempt {}!", label, attempt);
+                return Ok((cost, data_addr));
+            }
+            Err(e) => {
+                println!(
+                    "  Upload attempt {} for {} failed: {}. Retrying in 5 seconds...",
+                    attempt, label, e
+                );
+                if attempt == max_retries {
+                    return Err(eyre!("Failed to upload {} after {} attempts: {}", label, max_retries, e));
+                }
+                sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+
+    unreachable!("loop either returns or errors on the final attempt")
+}
 
+// Uploads a fully-assembled PublicArchive with retries.
+async fn upload_archive_with_retries(
+    client: &Client,
+    payment: &PaymentOption,
+    archive: &PublicArchive,
+) -> Result<(AttoTokens, ArchiveAddress)> {
     println!("Attempting to upload new PublicArchive with retries (max 50 attempts)...");
 
     let max_retries = 50;
-    let mut archive_upload_result: Option<(AttoTokens, ArchiveAddress)> = None;
 
     for attempt in 1..=max_retries {
         println!("  --- Archive Upload Attempt {}/{} ---", attempt, max_retries);
-        // Clone payment option for each attempt inside the loop
-        match client.archive_put_public(&archive, payment.clone()).await {
+        match client.archive_put_public(archive, payment.clone()).await {
             Ok((cost, archive_address)) => {
                 println!("  Successfully uploaded PublicArchive on attempt {}!", attempt);
-                archive_upload_result = Some((cost, archive_address));
-                break; // Exit loop on success
+                return Ok((cost, archive_address));
             }
             Err(e) => {
                 println!(
@@ -122,22 +539,49 @@ async fn perform_archive_action(
                     e
                 );
                 if attempt == max_retries {
-                    // Error already includes context from archive_put_public
-                    return Err(eyre::eyre!("Failed to upload archive after {} attempts: {}", max_retries, e));
+                    return Err(eyre!("Failed to upload archive after {} attempts: {}", max_retries, e));
                 }
                 sleep(Duration::from_secs(5)).await;
             }
         }
     }
 
-    // Check if archive upload succeeded
-    let (cost, archive_address) = archive_upload_result
-        .ok_or_else(|| eyre!("Archive upload failed after {} attempts.", max_retries))?;
+    unreachable!("loop either returns or errors on the final attempt")
+}
+
+// Function to use PublicArchive and add retries
+async fn perform_archive_action(
+    client: &Client,
+    payment: PaymentOption,
+    data_addr: &DataAddress,
+    original_path: &Path,
+    metadata: &Metadata
+) -> Result<ArchiveAddress> {
+    println!("--- Performing Archive Action (using PublicArchive) ---");
+    println!("Creating new archive for DataAddress: {:?} (original path: {:?})", data_addr, original_path);
+
+    let mut archive = PublicArchive::new();
+    let archive_path = original_path.file_name()
+        .ok_or_else(|| eyre!("Could not get filename for archive path"))?
+        .into();
+    archive.add_file(archive_path, *data_addr, metadata.clone());
+
+    let (cost, archive_address) = upload_archive_with_retries(client, &payment, &archive).await?;
 
     println!("Archive Upload successful!");
     println!("  Archive Cost: {} AttoTokens", cost);
     println!("  Archive Address: {:?}", archive_address);
 
+    if let Err(e) = append_ledger_entry(
+        LedgerEntryType::Archive,
+        original_path,
+        &hex::encode(archive_address.xorname().0),
+        cost,
+        None,
+    ) {
+        println!("Warning: Failed to record archive in upload ledger: {}", e);
+    }
+
     println!("--- Archive Action (PublicArchive) Complete ---");
     Ok(archive_address)
 }
@@ -147,10 +591,24 @@ async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
     let cli = Cli::parse();
 
+    // `List` only reads the local ledger, so it doesn't need a network client or wallet.
+    let command = match cli.command {
+        Commands::List(args) => return handle_list(args).await,
+        other => other,
+    };
+
     println!("Initializing client...");
     let client = Client::init().await.wrap_err("Failed to initialize client")?;
     println!("Client initialized.");
 
+    // `Download`, `Serve`, and `Mount` only read from the network, so they don't need a wallet.
+    let command = match command {
+        Commands::Download(args) => return handle_download(client, args).await,
+        Commands::Serve(args) => return handle_serve(client, args).await,
+        Commands::Mount(args) => return handle_mount(client, args).await,
+        other => other,
+    };
+
     println!("Setting up wallet from environment variable...");
     let pk_hex = env::var("AUTONOMI_PRIVATE_KEY")
         .map_err(|_| eyre!("AUTONOMI_PRIVATE_KEY environment variable not set."))?;
@@ -160,22 +618,27 @@ async fn main() -> Result<()> {
     let payment = PaymentOption::Wallet(wallet.clone());
     println!("Wallet setup complete using provided private key.");
 
-    match cli.command {
+    match command {
         Commands::Upload(args) => {
             handle_upload(client, payment, args).await?
         }
         Commands::Archive(args) => {
             handle_archive(client, payment, args).await?
         }
-        Commands::Download(args) => {
-            handle_download(client, args).await?
-        }
+        Commands::Download(_) => unreachable!("Download is handled before wallet setup"),
+        Commands::Serve(_) => unreachable!("Serve is handled before wallet setup"),
+        Commands::Mount(_) => unreachable!("Mount is handled before wallet setup"),
+        Commands::List(_) => unreachable!("List is handled before client initialization"),
     }
 
     Ok(())
 }
 
 async fn handle_upload(client: Client, payment: PaymentOption, args: UploadArgs) -> Result<()> {
+    if args.file_path.is_dir() {
+        return handle_upload_directory(client, payment, args).await;
+    }
+
     // --- File Reading & Metadata ---
     println!("Reading file: {:?}...", args.file_path);
     let file_content = read(&args.file_path)
@@ -192,46 +655,76 @@ async fn handle_upload(client: Client, payment: PaymentOption, args: UploadArgs)
     file_metadata.modified = system_time_now;
 
     println!("Read {} bytes from file.", original_data.len());
+    let sha256_hex = compute_sha256_hex(&original_data);
+
+    // --- Optional client-side encryption ---
+    let should_encrypt = args.encrypt || env::var("GEMS_PASSPHRASE").is_ok();
+    let passphrase = if should_encrypt {
+        Some(resolve_passphrase()?)
+    } else {
+        None
+    };
+    let upload_data = match &passphrase {
+        Some(passphrase) => {
+            println!("Encrypting file before upload...");
+            Bytes::from(encrypt_bytes(&original_data, passphrase)?)
+        }
+        None => original_data.clone(),
+    };
+    // Only the digest is needed from here on; release the original buffer so
+    // verification below never holds it alongside the fetched copy.
+    drop(original_data);
 
-    // --- Ask questions BEFORE upload --- 
+    // --- Ask questions BEFORE upload ---
     println!("\nConfiguration for after upload completes:");
     let should_verify = ask_yes_no("Download and verify the uploaded data afterwards?")?;
     let should_archive = ask_yes_no("Create a new archive for this upload afterwards?")?;
 
-    // --- Upload Loop ---
-    println!("\nAttempting to upload file with retries (max 50 attempts)...");
-    let max_retries = 50;
-    let mut upload_result: Option<(AttoTokens, DataAddress)> = None;
+    // --- Dedup check (skipped for encrypted uploads, whose ciphertext never repeats) ---
+    let mut dedup_index = load_dedup_index()?;
+    let dedup_hit = if should_encrypt {
+        None
+    } else {
+        dedup_index.get(&sha256_hex).cloned()
+    };
 
-    for attempt in 1..=max_retries {
-        println!("\n--- Upload Attempt {}/{} ---", attempt, max_retries);
-        match client
-            .data_put_public(original_data.clone(), payment.clone()) // Clone payment for loop
-            .await
-        {
-            Ok((cost, data_addr)) => {
-                println!("Upload successful on attempt {}!", attempt);
-                upload_result = Some((cost, data_addr));
-                break;
-            }
-            Err(e) => {
-                println!("Upload attempt {} failed: {}. Retrying in 5 seconds...", attempt, e);
-                if attempt == max_retries {
-                    return Err(eyre!("Failed to upload file after {} attempts: {}", max_retries, e));
-                }
-                sleep(Duration::from_secs(5)).await;
+    let (cost, data_addr) = if let Some((existing_addr_hex, previous_cost_display)) = dedup_hit {
+        drop(upload_data);
+        let data_addr = data_address_from_hex(&existing_addr_hex)?;
+        println!(
+            "\nIdentical content already stored at {:?}; skipping upload (would have cost ~{} AttoTokens).",
+            data_addr, previous_cost_display
+        );
+        (AttoTokens::from(0), data_addr)
+    } else {
+        // --- Upload Loop ---
+        println!("\nAttempting to upload file with retries (max 50 attempts)...");
+        let label = format!("{:?}", args.file_path);
+        let (cost, data_addr) = upload_bytes_with_retries(&client, &payment, upload_data, &label).await?;
+
+        println!("\nUpload successful!");
+        println!("  Cost: {} AttoTokens", cost);
+        println!("  Data Address: {:?}", data_addr);
+
+        if !should_encrypt {
+            dedup_index.insert(sha256_hex.clone(), (hex::encode(data_addr.xorname().0), cost.to_string()));
+            if let Err(e) = save_dedup_index(&dedup_index) {
+                println!("Warning: Failed to update dedup index: {}", e);
             }
         }
-    }
 
-    // Check if upload succeeded
-    let (cost, data_addr) = upload_result
-        .ok_or_else(|| eyre!("Upload failed after {} attempts.", max_retries))?;
+        (cost, data_addr)
+    };
 
-    // If upload succeeded, proceed based on answers given earlier
-    println!("\nUpload successful!");
-    println!("  Cost: {} AttoTokens", cost);
-    println!("  Data Address: {:?}", data_addr);
+    if let Err(e) = append_ledger_entry(
+        LedgerEntryType::Data,
+        &args.file_path,
+        &hex::encode(data_addr.xorname().0),
+        cost,
+        Some(&sha256_hex),
+    ) {
+        println!("Warning: Failed to record upload in upload ledger: {}", e);
+    }
 
     // --- Conditional Download/Verification (based on earlier answer) ---
     if should_verify {
@@ -241,24 +734,29 @@ async fn handle_upload(client: Client, payment: PaymentOption, args: UploadArgs)
             Ok(fetched_data) => {
                 println!("Download successful! Fetched {} bytes.", fetched_data.len());
                 println!("Verifying downloaded data...");
-                if original_data == fetched_data {
-                    println!("Verification successful: Original and downloaded data match.");
+                // A verification-step decryption failure is logged rather than propagated
+                // with `?`, so it can't abort an otherwise-successful upload (and skip the
+                // archive-creation step below).
+                let fetched_data = match &passphrase {
+                    Some(passphrase) => match decrypt_bytes(&fetched_data, passphrase) {
+                        Ok(plaintext) => Some(Bytes::from(plaintext)),
+                        Err(e) => {
+                            println!("Error decrypting downloaded data during verification: {}. Skipping verification.", e);
+                            None
+                        }
+                    },
+                    None => Some(fetched_data),
+                };
+                if let Some(fetched_data) = fetched_data {
+                    // Streams the fetched data straight to disk, hashing as it goes, instead of
+                    // holding both the original and fetched buffers in memory to compare them.
                     let output_filename = args.output_dir.join(
                         args.file_path.file_name().ok_or_else(|| eyre!("Could not get filename"))?
                     );
-                    println!("Saving verified file to {:?}", output_filename);
-                    if let Err(e) = write(&output_filename, original_data).await {
-                         println!("Warning: Failed to write verified file: {}", e);
-                    }
-                } else {
-                    println!("Verification failed: Data mismatch!");
-                    // Save downloaded file for inspection even on mismatch
-                    let output_filename = args.output_dir.join(
-                        args.file_path.file_name().ok_or_else(|| eyre!("Could not get filename"))?.to_str().unwrap_or("downloaded_file_error").to_owned() + ".mismatched"
-                    );
-                    println!("Saving mismatched downloaded file to {:?} for inspection.", output_filename);
-                    if let Err(e) = write(&output_filename, fetched_data).await {
-                        println!("Warning: Failed to write mismatched downloaded file: {}", e);
+                    println!("Saving and verifying file to {:?}", output_filename);
+                    match write_with_hash_verification(fetched_data, output_filename.clone(), Some(sha256_hex.clone())).await {
+                        Ok(()) => println!("Verification successful: downloaded data matches the uploaded sha256 digest."),
+                        Err(e) => println!("Verification failed: {}", e),
                     }
                 }
             }
@@ -297,6 +795,252 @@ async fn handle_upload(client: Client, payment: PaymentOption, args: UploadArgs)
     Ok(())
 }
 
+// Recursively walks `args.file_path`, uploads every regular file it finds,
+// and assembles the results into a single PublicArchive whose entry paths
+// are relative to the upload root (so the directory structure survives).
+async fn handle_upload_directory(client: Client, payment: PaymentOption, args: UploadArgs) -> Result<()> {
+    let root = args.file_path.clone();
+    println!("Walking directory: {:?}...", root);
+
+    let should_encrypt = args.encrypt || env::var("GEMS_PASSPHRASE").is_ok();
+    let passphrase = if should_encrypt {
+        Some(resolve_passphrase()?)
+    } else {
+        None
+    };
+
+    let mut dedup_index = load_dedup_index()?;
+    let mut dedup_hits = 0u32;
+    let mut dedup_bytes_saved = 0u64;
+    let mut dedup_atto_saved = 0u128;
+
+    let mut archive = PublicArchive::new();
+    let mut file_count = 0;
+
+    for entry in WalkDir::new(&root).follow_links(false) {
+        // Each file is uploaded (and retried) independently: a bad walk
+        // entry, an unreadable file, or one that exhausts its own 50 upload
+        // retries is logged and skipped rather than aborting the whole walk
+        // and losing the files already paid for and uploaded before it.
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                println!("Warning: failed to walk directory entry: {}; skipping.", e);
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let absolute_path = entry.path();
+        let relative_path = match absolute_path.strip_prefix(&root) {
+            Ok(p) => p.to_path_buf(),
+            Err(e) => {
+                println!("Warning: failed to relativize path {:?}: {}; skipping.", absolute_path, e);
+                continue;
+            }
+        };
+
+        let fs_metadata = match entry.metadata() {
+            Ok(m) => m,
+            Err(e) => {
+                println!("Warning: failed to read filesystem metadata for {:?}: {}; skipping.", absolute_path, e);
+                continue;
+            }
+        };
+
+        let mut file_metadata = Metadata::new_with_size(fs_metadata.len());
+        file_metadata.created = system_time_to_unix_secs(fs_metadata.created().ok());
+        file_metadata.modified = system_time_to_unix_secs(fs_metadata.modified().ok());
+
+        println!("\nReading file: {:?}...", absolute_path);
+        let file_content = match read(absolute_path).await {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Warning: failed to read file {:?}: {}; skipping.", absolute_path, e);
+                continue;
+            }
+        };
+        let file_data = Bytes::from(file_content);
+
+        let sha256_hex = compute_sha256_hex(&file_data);
+        let file_byte_len = file_data.len() as u64;
+
+        // Dedup is only meaningful on plaintext content: encrypted blobs get
+        // a fresh random nonce every time, so identical plaintext never
+        // produces identical ciphertext to dedup against.
+        let dedup_hit = if should_encrypt { None } else { dedup_index.get(&sha256_hex).cloned() };
+
+        let (cost, data_addr) = if let Some((existing_addr_hex, previous_cost_display)) = dedup_hit {
+            let data_addr = match data_address_from_hex(&existing_addr_hex) {
+                Ok(addr) => addr,
+                Err(e) => {
+                    println!("Warning: stale dedup entry for {:?}: {}; skipping.", absolute_path, e);
+                    continue;
+                }
+            };
+            println!(
+                "  Identical content already stored at {:?}; skipping upload (would have cost ~{} AttoTokens).",
+                data_addr, previous_cost_display
+            );
+            dedup_hits += 1;
+            dedup_bytes_saved += file_byte_len;
+            dedup_atto_saved += previous_cost_display.parse::<u128>().unwrap_or(0);
+            (AttoTokens::from(0), data_addr)
+        } else {
+            let upload_data = match &passphrase {
+                Some(passphrase) => match encrypt_bytes(&file_data, passphrase) {
+                    Ok(ciphertext) => Bytes::from(ciphertext),
+                    Err(e) => {
+                        println!("Warning: failed to encrypt {:?}: {}; skipping.", absolute_path, e);
+                        continue;
+                    }
+                },
+                None => file_data,
+            };
+            let label = format!("{:?}", relative_path);
+            let (cost, data_addr) = match upload_bytes_with_retries(&client, &payment, upload_data, &label).await {
+                Ok(result) => result,
+                Err(e) => {
+                    println!("Warning: failed to upload {:?} after exhausting retries: {}; skipping.", absolute_path, e);
+                    continue;
+                }
+            };
+
+            if !should_encrypt {
+                dedup_index.insert(sha256_hex.clone(), (hex::encode(data_addr.xorname().0), cost.to_string()));
+                // Persisted after every new upload, not just once at the end of the walk,
+                // so a later file's failure can't discard dedup credit for files that
+                // already succeeded earlier in this same run.
+                if let Err(e) = save_dedup_index(&dedup_index) {
+                    println!("Warning: Failed to update dedup index: {}", e);
+                }
+            }
+
+            (cost, data_addr)
+        };
+
+        if let Err(e) = append_ledger_entry(
+            LedgerEntryType::Data,
+            absolute_path,
+            &hex::encode(data_addr.xorname().0),
+            cost,
+            Some(&sha256_hex),
+        ) {
+            println!("Warning: Failed to record upload in upload ledger: {}", e);
+        }
+
+        archive.add_file(relative_path, data_addr, file_metadata);
+        file_count += 1;
+    }
+
+    if dedup_hits > 0 {
+        println!(
+            "\nDedup: skipped {} duplicate file(s), avoiding re-upload of {} bytes (~{} AttoTokens saved).",
+            dedup_hits, dedup_bytes_saved, dedup_atto_saved
+        );
+    }
+
+    if file_count == 0 {
+        return Err(eyre!("No regular files found under directory: {:?}", root));
+    }
+
+    println!("\nUploaded {} file(s). Uploading directory archive...", file_count);
+    let (archive_cost, archive_address) = upload_archive_with_retries(&client, &payment, &archive).await?;
+
+    println!("\nDirectory archive upload successful!");
+    println!("  Archive Cost: {} AttoTokens", archive_cost);
+    println!("  Archive Address: {:?}", archive_address);
+
+    if let Err(e) = append_ledger_entry(
+        LedgerEntryType::Archive,
+        &root,
+        &hex::encode(archive_address.xorname().0),
+        archive_cost,
+        None,
+    ) {
+        println!("Warning: Failed to record archive in upload ledger: {}", e);
+    }
+
+    println!("\nUpload process completed.");
+    Ok(())
+}
+
+fn system_time_to_unix_secs(time: Option<SystemTime>) -> u64 {
+    time.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn compute_sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+// Writes `data` to `target_path`, hashing it in fixed-size chunks as it goes
+// (BufWriter + a rolling hasher) rather than buffering a second full copy
+// just to verify it. If `expected_hex` is given (callers pass `--verify-hash`
+// or the digest they looked up in the upload ledger), mismatches fail loudly
+// and the partial file is renamed to `*.corrupt` instead of being left
+// looking like a good download. With `None`, nothing is compared.
+async fn write_with_hash_verification(
+    data: Bytes,
+    target_path: PathBuf,
+    expected_hex: Option<String>,
+) -> Result<()> {
+    let write_target = target_path.clone();
+    let digest_hex = tokio::task::spawn_blocking(move || -> Result<String> {
+        if let Some(parent) = write_target.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent)
+                    .wrap_err_with(|| format!("Failed to create output directory: {:?}", parent))?;
+            }
+        }
+
+        let file = File::create(&write_target)
+            .wrap_err_with(|| format!("Failed to create output file: {:?}", write_target))?;
+        let mut writer = BufWriter::new(file);
+        let mut hasher = Sha256::new();
+
+        const CHUNK_SIZE: usize = 64 * 1024;
+        for chunk in data.chunks(CHUNK_SIZE) {
+            hasher.update(chunk);
+            writer
+                .write_all(chunk)
+                .wrap_err_with(|| format!("Failed to write to output file: {:?}", write_target))?;
+        }
+        writer
+            .flush()
+            .wrap_err_with(|| format!("Failed to flush output file: {:?}", write_target))?;
+
+        Ok(hex::encode(hasher.finalize()))
+    })
+    .await
+    .wrap_err("Hash-while-writing task panicked")??;
+
+    match expected_hex {
+        Some(expected) if !expected.eq_ignore_ascii_case(&digest_hex) => {
+            let mut corrupt_path = target_path.clone().into_os_string();
+            corrupt_path.push(".corrupt");
+            let corrupt_path = PathBuf::from(corrupt_path);
+            std::fs::rename(&target_path, &corrupt_path)
+                .wrap_err_with(|| format!("Failed to rename corrupt download to {:?}", corrupt_path))?;
+            Err(eyre!(
+                "Integrity check failed for {:?}: expected sha256 {}, got {}. Renamed to {:?}",
+                target_path, expected, digest_hex, corrupt_path
+            ))
+        }
+        Some(_) => {
+            println!("    Integrity verified (sha256 {}).", digest_hex);
+            Ok(())
+        }
+        None => {
+            println!("    Wrote file (sha256 {}, no expected digest to compare against).", digest_hex);
+            Ok(())
+        }
+    }
+}
+
 // Updated to create a new archive for a given DataAddress
 async fn handle_archive(client: Client, payment: PaymentOption, args: ArchiveArgs) -> Result<()> {
     println!("Attempting to create new archive for data address: {}", args.data_address_hex);
@@ -352,6 +1096,13 @@ async fn handle_download(client: Client, args: DownloadArgs) -> Result<()> {
     
     let addr = DataAddress::new(XorName(xorname_array));
 
+    if args.archive && args.verify_hash.is_some() {
+        return Err(eyre!(
+            "--verify-hash applies to a single file's digest and is ambiguous against an archive's many entries; \
+             omit --archive to verify a single file, or rely on the per-entry digests already recorded in the upload ledger."
+        ));
+    }
+
     if args.archive {
         // --- Download Archive Contents ---
         println!("Fetching archive data from {:?}...", addr);
@@ -373,7 +1124,8 @@ async fn handle_download(client: Client, args: DownloadArgs) -> Result<()> {
 
         let mut success_count = 0;
         let mut error_count = 0;
-        
+        let mut passphrase_cache: Option<String> = None;
+
         for (item_path, item_data_addr, _metadata) in archive.iter() {
             let target_file_path = args.output_path.join(item_path);
             println!("  Downloading {:?} (from {:?}) -> {:?}", item_path, item_data_addr, target_file_path);
@@ -388,7 +1140,15 @@ async fn handle_download(client: Client, args: DownloadArgs) -> Result<()> {
 
             match client.data_get_public(item_data_addr).await {
                 Ok(item_bytes) => {
-                    match write(&target_file_path, item_bytes).await {
+                    let item_addr_hex = hex::encode(item_data_addr.xorname().0);
+                    let expected_hex = lookup_expected_hash(&item_addr_hex).unwrap_or(None);
+                    let save_result = match decrypt_if_needed(item_bytes, &mut passphrase_cache) {
+                        Ok(item_bytes) => {
+                            write_with_hash_verification(item_bytes, target_file_path.clone(), expected_hex).await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    match save_result {
                         Ok(_) => {
                             println!("    Successfully saved {:?}", target_file_path);
                             success_count += 1;
@@ -421,11 +1181,507 @@ async fn handle_download(client: Client, args: DownloadArgs) -> Result<()> {
                      .wrap_err_with(|| format!("Failed to create output directory: {:?}", parent_dir))?;
              }
          }
-        
-        write(&args.output_path, fetched_bytes).await
+
+        let expected_hex = args
+            .verify_hash
+            .clone()
+            .or_else(|| lookup_expected_hash(&args.address_hex).unwrap_or(None));
+
+        let mut passphrase_cache: Option<String> = None;
+        let fetched_bytes = decrypt_if_needed(fetched_bytes, &mut passphrase_cache)?;
+
+        write_with_hash_verification(fetched_bytes, args.output_path.clone(), expected_hex).await
             .wrap_err_with(|| format!("Failed to write downloaded file to: {:?}", args.output_path))?;
         println!("Successfully downloaded and saved single file.");
     }
 
     Ok(())
 } 
+
+// --- HTTP Serving ---
+// Turns an uploaded archive into a browsable, read-only website without
+// downloading the whole thing up front: files are fetched from the network
+// lazily, on first request, and kept in a small in-memory LRU cache so hot
+// files don't get re-fetched on every hit.
+
+struct ServeState {
+    client: Client,
+    entries: HashMap<String, (DataAddress, Metadata)>,
+    order: Vec<String>,
+    cache: AsyncMutex<LruCache<XorName, Bytes>>,
+}
+
+// Sniffs the first few KB of a blob to guess its Content-Type, rather than
+// trusting the filename (archive entries carry no extension guarantee).
+fn sniff_content_type(bytes: &[u8]) -> &'static str {
+    let head = &bytes[..bytes.len().min(4096)];
+
+    if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return "image/png";
+    }
+    if head.starts_with(b"\xFF\xD8\xFF") {
+        return "image/jpeg";
+    }
+    if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        return "image/gif";
+    }
+    if head.starts_with(b"%PDF-") {
+        return "application/pdf";
+    }
+    if head.starts_with(b"PK\x03\x04") {
+        return "application/zip";
+    }
+    if head.starts_with(b"<?xml") {
+        return "application/xml";
+    }
+
+    match std::str::from_utf8(head) {
+        Ok(text) if text.trim_start().starts_with("<!DOCTYPE html") || text.trim_start().starts_with("<html") => {
+            "text/html; charset=utf-8"
+        }
+        Ok(_) => "text/plain; charset=utf-8",
+        Err(_) => "application/octet-stream",
+    }
+}
+
+async fn handle_serve(client: Client, args: ServeArgs) -> Result<()> {
+    println!("Fetching archive from address: {}", args.address_hex);
+
+    let xorname_bytes = hex::decode(&args.address_hex)
+        .wrap_err("Invalid hex string for ArchiveAddress XorName")?;
+    let xorname_array: [u8; 32] = xorname_bytes.as_slice().try_into()
+        .map_err(|_| eyre!("Hex string does not represent a valid XorName (expected 32 bytes, got {})", xorname_bytes.len()))?;
+    let addr = DataAddress::new(XorName(xorname_array));
+
+    let fetched_archive_bytes = client.data_get_public(&addr).await
+        .wrap_err_with(|| format!("Failed to get public data for archive address: {:?}", addr))?;
+    let archive = PublicArchive::from_bytes(fetched_archive_bytes)
+        .wrap_err("Failed to deserialize PublicArchive data")?;
+
+    let mut entries = HashMap::new();
+    let mut order = Vec::new();
+    for (item_path, item_data_addr, item_metadata) in archive.iter() {
+        let url_path = item_path.to_string_lossy().replace('\\', "/");
+        order.push(url_path.clone());
+        entries.insert(url_path, (*item_data_addr, item_metadata.clone()));
+    }
+    order.sort();
+
+    let cache_size = std::num::NonZeroUsize::new(args.cache_size.max(1)).expect("cache_size clamped to >= 1");
+    let state = Arc::new(ServeState {
+        client,
+        entries,
+        order,
+        cache: AsyncMutex::new(LruCache::new(cache_size)),
+    });
+
+    let app = Router::new()
+        .route("/", get(serve_index))
+        .route("/*path", get(serve_file))
+        .with_state(state);
+
+    let bind_addr = SocketAddr::from(([127, 0, 0, 1], args.port));
+    println!("Serving archive {:?} at http://{}/", addr, bind_addr);
+    let listener = tokio::net::TcpListener::bind(bind_addr)
+        .await
+        .wrap_err_with(|| format!("Failed to bind to {}", bind_addr))?;
+    axum::serve(listener, app)
+        .await
+        .wrap_err("HTTP server exited with an error")?;
+
+    Ok(())
+}
+
+async fn serve_index(State(state): State<Arc<ServeState>>) -> Html<String> {
+    let mut body = String::from("<!DOCTYPE html><html><head><title>gems archive</title></head><body><h1>Archive contents</h1><ul>");
+    for path in &state.order {
+        body.push_str(&format!("<li><a href=\"/{path}\">{path}</a></li>"));
+    }
+    body.push_str("</ul></body></html>");
+    Html(body)
+}
+
+async fn serve_file(State(state): State<Arc<ServeState>>, AxumPath(path): AxumPath<String>) -> Response {
+    let Some((data_addr, _metadata)) = state.entries.get(&path) else {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    };
+    let xorname = data_addr.xorname();
+
+    {
+        let mut cache = state.cache.lock().await;
+        if let Some(cached) = cache.get(&xorname) {
+            let content_type = sniff_content_type(cached);
+            return ([(header::CONTENT_TYPE, content_type)], cached.clone()).into_response();
+        }
+    }
+
+    let bytes = match state.client.data_get_public(data_addr).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            println!("Error fetching {:?} ({:?}): {}", path, data_addr, e);
+            return (StatusCode::BAD_GATEWAY, "Failed to fetch file from the network").into_response();
+        }
+    };
+
+    // Serve only hands out plaintext: decrypting on every request would mean prompting
+    // for a passphrase from an HTTP handler, so an encrypted entry fails loudly instead
+    // of sniffing and serving raw ciphertext with a bogus content-type.
+    if has_encryption_header(&bytes) {
+        println!("Error: {:?} was uploaded with --encrypt; serve does not support encrypted files.", path);
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "This file was uploaded with --encrypt and cannot be served over HTTP; use `gems download` to decrypt it locally.",
+        )
+            .into_response();
+    }
+
+    let content_type = sniff_content_type(&bytes);
+    let response = ([(header::CONTENT_TYPE, content_type)], bytes.clone()).into_response();
+
+    let mut cache = state.cache.lock().await;
+    cache.put(xorname, bytes);
+
+    response
+}
+
+// --- FUSE Mount ---
+// Mounts an archive as a read-only filesystem: the directory tree is built
+// once, up front, from the archive's entry paths and Metadata, so `stat` and
+// directory listings resolve instantly from the in-memory map. A file's
+// bytes are fetched with `data_get_public` when the file is opened, not when
+// it is listed or stat'ed; an open() that is never followed by a read()
+// still fetches the whole file. Fetched blobs are cached by XorName under a
+// configurable memory budget so repeated opens are cheap.
+
+const FUSE_ATTR_TTL: Duration = Duration::from_secs(1);
+
+enum FsEntryKind {
+    Dir { children: Vec<u64> },
+    File { data_addr: DataAddress, size: u64, mtime_secs: u64 },
+}
+
+struct FsEntry {
+    name: String,
+    parent: u64,
+    kind: FsEntryKind,
+}
+
+const FUSE_ROOT_INO: u64 = 1;
+
+// Builds the inode tree for a mounted archive: one inode per directory
+// component (shared across files that live under the same directory) plus
+// one inode per file entry.
+fn build_inode_tree(archive: &PublicArchive) -> HashMap<u64, FsEntry> {
+    let mut entries = HashMap::new();
+    entries.insert(
+        FUSE_ROOT_INO,
+        FsEntry { name: "/".to_string(), parent: FUSE_ROOT_INO, kind: FsEntryKind::Dir { children: Vec::new() } },
+    );
+
+    let mut next_inode = FUSE_ROOT_INO + 1;
+    let mut dir_inodes: HashMap<PathBuf, u64> = HashMap::new();
+    dir_inodes.insert(PathBuf::new(), FUSE_ROOT_INO);
+
+    for (item_path, item_data_addr, item_metadata) in archive.iter() {
+        let mut current_dir_ino = FUSE_ROOT_INO;
+        let mut current_path = PathBuf::new();
+        let components: Vec<_> = item_path.components().collect();
+
+        for (i, component) in components.iter().enumerate() {
+            current_path.push(component.as_os_str());
+            let name = component.as_os_str().to_string_lossy().to_string();
+
+            if i == components.len() - 1 {
+                let ino = next_inode;
+                next_inode += 1;
+                entries.insert(
+                    ino,
+                    FsEntry {
+                        name,
+                        parent: current_dir_ino,
+                        kind: FsEntryKind::File {
+                            data_addr: *item_data_addr,
+                            size: item_metadata.size,
+                            mtime_secs: item_metadata.modified,
+                        },
+                    },
+                );
+                if let Some(FsEntry { kind: FsEntryKind::Dir { children }, .. }) = entries.get_mut(&current_dir_ino) {
+                    children.push(ino);
+                }
+            } else if let Some(&existing_ino) = dir_inodes.get(&current_path) {
+                current_dir_ino = existing_ino;
+            } else {
+                let ino = next_inode;
+                next_inode += 1;
+                entries.insert(ino, FsEntry { name, parent: current_dir_ino, kind: FsEntryKind::Dir { children: Vec::new() } });
+                if let Some(FsEntry { kind: FsEntryKind::Dir { children }, .. }) = entries.get_mut(&current_dir_ino) {
+                    children.push(ino);
+                }
+                dir_inodes.insert(current_path.clone(), ino);
+                current_dir_ino = ino;
+            }
+        }
+    }
+
+    entries
+}
+
+fn make_file_attr(ino: u64, entry: &FsEntry) -> FileAttr {
+    match &entry.kind {
+        FsEntryKind::Dir { .. } => {
+            let now = SystemTime::now();
+            FileAttr {
+                ino, size: 0, blocks: 0, atime: now, mtime: now, ctime: now, crtime: now,
+                kind: FileType::Directory, perm: 0o555, nlink: 2,
+                uid: 0, gid: 0, rdev: 0, blksize: 512, flags: 0,
+            }
+        }
+        FsEntryKind::File { size, mtime_secs, .. } => {
+            let mtime = UNIX_EPOCH + Duration::from_secs(*mtime_secs);
+            FileAttr {
+                ino, size: *size, blocks: size.div_ceil(512), atime: mtime, mtime, ctime: mtime, crtime: mtime,
+                kind: FileType::RegularFile, perm: 0o444, nlink: 1,
+                uid: 0, gid: 0, rdev: 0, blksize: 512, flags: 0,
+            }
+        }
+    }
+}
+
+// An LRU cache that evicts by total cached bytes rather than by entry count,
+// so `--cache-budget-mb` bounds the bytes held in this cache regardless of
+// how large or small the archive's individual files are. File contents held
+// for open file handles (`GemsFs::open_files`) are not counted against it.
+struct ByteBudgetedCache {
+    lru: LruCache<XorName, Bytes>,
+    used_bytes: u64,
+    budget_bytes: u64,
+}
+
+impl ByteBudgetedCache {
+    fn new(budget_bytes: u64) -> Self {
+        Self { lru: LruCache::unbounded(), used_bytes: 0, budget_bytes }
+    }
+
+    fn get(&mut self, key: &XorName) -> Option<Bytes> {
+        self.lru.get(key).cloned()
+    }
+
+    fn put(&mut self, key: XorName, value: Bytes) {
+        let len = value.len() as u64;
+        if len > self.budget_bytes {
+            // Larger than the whole budget: serve it uncached rather than evicting everything else.
+            return;
+        }
+        while self.used_bytes + len > self.budget_bytes {
+            match self.lru.pop_lru() {
+                Some((_, evicted)) => self.used_bytes = self.used_bytes.saturating_sub(evicted.len() as u64),
+                None => break,
+            }
+        }
+        self.lru.put(key, value);
+        self.used_bytes += len;
+    }
+}
+
+struct GemsFs {
+    entries: HashMap<u64, FsEntry>,
+    client: Arc<Client>,
+    runtime: tokio::runtime::Handle,
+    cache: SyncMutex<ByteBudgetedCache>,
+    // Whole-file bytes for every currently-open file descriptor, keyed by
+    // file handle. Independent of `cache`'s eviction budget: a file bigger
+    // than the budget still gets fetched exactly once per open() and held
+    // here for the lifetime of the descriptor, rather than being re-fetched
+    // from the network on every individual read() call against it.
+    open_files: SyncMutex<HashMap<u64, Bytes>>,
+    next_fh: SyncMutex<u64>,
+}
+
+impl Filesystem for GemsFs {
+    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let Some(parent_entry) = self.entries.get(&parent) else {
+            reply.error(ENOENT);
+            return;
+        };
+        let FsEntryKind::Dir { children } = &parent_entry.kind else {
+            reply.error(ENOTDIR);
+            return;
+        };
+
+        let wanted = name.to_string_lossy();
+        for &child_ino in children {
+            if let Some(child) = self.entries.get(&child_ino) {
+                if child.name == wanted {
+                    reply.entry(&FUSE_ATTR_TTL, &make_file_attr(child_ino, child), 0);
+                    return;
+                }
+            }
+        }
+        reply.error(ENOENT);
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.entries.get(&ino) {
+            Some(entry) => reply.attr(&FUSE_ATTR_TTL, &make_file_attr(ino, entry)),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn readdir(&mut self, _req: &Request, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+        let Some(entry) = self.entries.get(&ino) else {
+            reply.error(ENOENT);
+            return;
+        };
+        let FsEntryKind::Dir { children } = &entry.kind else {
+            reply.error(ENOTDIR);
+            return;
+        };
+
+        let mut listing = vec![(ino, FileType::Directory, ".".to_string()), (entry.parent, FileType::Directory, "..".to_string())];
+        for &child_ino in children {
+            if let Some(child) = self.entries.get(&child_ino) {
+                let kind = match child.kind {
+                    FsEntryKind::Dir { .. } => FileType::Directory,
+                    FsEntryKind::File { .. } => FileType::RegularFile,
+                };
+                listing.push((child_ino, kind, child.name.clone()));
+            }
+        }
+
+        for (i, (child_ino, kind, name)) in listing.into_iter().enumerate().skip(offset as usize) {
+            if reply.add(child_ino, (i + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    // Fetches the whole file exactly once per open() — rather than once per
+    // read() — and holds it in `open_files` for the lifetime of the file
+    // handle, independent of whether it fits under the LRU's cache budget.
+    fn open(&mut self, _req: &Request, ino: u64, _flags: i32, reply: ReplyOpen) {
+        let Some(entry) = self.entries.get(&ino) else {
+            reply.error(ENOENT);
+            return;
+        };
+        let FsEntryKind::File { data_addr, .. } = &entry.kind else {
+            reply.error(EISDIR);
+            return;
+        };
+        let data_addr = *data_addr;
+        let xorname = data_addr.xorname();
+
+        let cached = self.cache.lock().expect("cache mutex poisoned").get(&xorname);
+        let bytes = match cached {
+            Some(bytes) => bytes,
+            None => {
+                let client = Arc::clone(&self.client);
+                let fetched = self.runtime.block_on(async move { client.data_get_public(&data_addr).await });
+                match fetched {
+                    Ok(bytes) => {
+                        // Mount only serves plaintext: an encrypted entry would report the
+                        // archive's (plaintext) size via getattr while read() handed back the
+                        // longer ciphertext blob, silently corrupting stat/cp/grep.
+                        if has_encryption_header(&bytes) {
+                            println!("Error: {:?} was uploaded with --encrypt; mount does not support encrypted files. Use `gems download` instead.", data_addr);
+                            reply.error(EACCES);
+                            return;
+                        }
+                        self.cache.lock().expect("cache mutex poisoned").put(xorname, bytes.clone());
+                        bytes
+                    }
+                    Err(e) => {
+                        println!("Error fetching {:?} for open: {}", data_addr, e);
+                        reply.error(EIO);
+                        return;
+                    }
+                }
+            }
+        };
+
+        let fh = {
+            let mut next_fh = self.next_fh.lock().expect("fh counter mutex poisoned");
+            let fh = *next_fh;
+            *next_fh += 1;
+            fh
+        };
+        self.open_files.lock().expect("open files mutex poisoned").insert(fh, bytes);
+        reply.opened(fh, 0);
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Some(bytes) = self.open_files.lock().expect("open files mutex poisoned").get(&fh).cloned() else {
+            println!("Error: read() called with no open file handle {}", fh);
+            reply.error(EIO);
+            return;
+        };
+
+        let start = (offset as usize).min(bytes.len());
+        let end = start.saturating_add(size as usize).min(bytes.len());
+        reply.data(&bytes[start..end]);
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.open_files.lock().expect("open files mutex poisoned").remove(&fh);
+        reply.ok();
+    }
+}
+
+async fn handle_mount(client: Client, args: MountArgs) -> Result<()> {
+    println!("Fetching archive from address: {}", args.address_hex);
+
+    let xorname_bytes = hex::decode(&args.address_hex)
+        .wrap_err("Invalid hex string for ArchiveAddress XorName")?;
+    let xorname_array: [u8; 32] = xorname_bytes.as_slice().try_into()
+        .map_err(|_| eyre!("Hex string does not represent a valid XorName (expected 32 bytes, got {})", xorname_bytes.len()))?;
+    let addr = DataAddress::new(XorName(xorname_array));
+
+    let fetched_archive_bytes = client.data_get_public(&addr).await
+        .wrap_err_with(|| format!("Failed to get public data for archive address: {:?}", addr))?;
+    let archive = PublicArchive::from_bytes(fetched_archive_bytes)
+        .wrap_err("Failed to deserialize PublicArchive data")?;
+
+    let entries = build_inode_tree(&archive);
+
+    let cache_budget_bytes = args.cache_budget_mb.saturating_mul(1024 * 1024);
+
+    let fs = GemsFs {
+        entries,
+        client: Arc::new(client),
+        runtime: tokio::runtime::Handle::current(),
+        cache: SyncMutex::new(ByteBudgetedCache::new(cache_budget_bytes)),
+        open_files: SyncMutex::new(HashMap::new()),
+        next_fh: SyncMutex::new(1),
+    };
+
+    let mountpoint = args.mountpoint.clone();
+    println!("Mounting archive {:?} read-only at {:?}...", addr, mountpoint);
+    let options = vec![MountOption::RO, MountOption::FSName("gems".to_string())];
+
+    tokio::task::spawn_blocking(move || fuser::mount2(fs, &mountpoint, &options))
+        .await
+        .wrap_err("FUSE mount task panicked")?
+        .wrap_err_with(|| format!("Failed to mount FUSE filesystem at {:?}", args.mountpoint))?;
+
+    Ok(())
+}